@@ -0,0 +1,137 @@
+use pgwire::{
+    api::{Type, results::FieldFormat},
+    error::{PgWireError, PgWireResult},
+};
+use sqlparser::ast::{Expr, Statement, Value as AstValue, VisitMut, VisitorMut};
+use std::ops::ControlFlow;
+
+use crate::{SqlState, nexus_error};
+
+/// Resolves the wire format of bind parameter `i` from the format codes
+/// carried by a `Bind` message, which may supply zero codes (all text),
+/// one code (applied to every parameter), or one code per parameter.
+fn format_for(formats: &[FieldFormat], i: usize) -> FieldFormat {
+    match formats {
+        [] => FieldFormat::Text,
+        [format] => *format,
+        formats => formats.get(i).copied().unwrap_or(FieldFormat::Text),
+    }
+}
+
+fn parse_be<const N: usize>(ty: &Type, bytes: &[u8]) -> PgWireResult<[u8; N]> {
+    bytes.try_into().map_err(|_| {
+        nexus_error(
+            SqlState::InvalidBinaryRepresentation,
+            format!(
+                "malformed binary parameter value for type {ty:?}: expected {N} bytes, got {}",
+                bytes.len()
+            ),
+        )
+    })
+}
+
+fn number_literal(value: impl ToString) -> Expr {
+    Expr::Value(AstValue::Number(value.to_string(), false))
+}
+
+fn decode_binary_param(ty: &Type, bytes: &[u8]) -> PgWireResult<Expr> {
+    match *ty {
+        Type::BOOL => Ok(Expr::Value(AstValue::Boolean(
+            bytes.first().copied().unwrap_or(0) != 0,
+        ))),
+        Type::INT2 => Ok(number_literal(i16::from_be_bytes(parse_be(ty, bytes)?))),
+        Type::INT4 => Ok(number_literal(i32::from_be_bytes(parse_be(ty, bytes)?))),
+        Type::INT8 => Ok(number_literal(i64::from_be_bytes(parse_be(ty, bytes)?))),
+        Type::FLOAT4 => Ok(number_literal(f32::from_be_bytes(parse_be(ty, bytes)?))),
+        Type::FLOAT8 => Ok(number_literal(f64::from_be_bytes(parse_be(ty, bytes)?))),
+        Type::TEXT | Type::VARCHAR => std::str::from_utf8(bytes)
+            .map(|s| Expr::Value(AstValue::SingleQuotedString(s.to_owned())))
+            .map_err(|e| PgWireError::ApiError(Box::new(e))),
+        _ => Err(nexus_error(
+            SqlState::FeatureNotSupported,
+            format!("binary parameter format is not supported for type {ty:?}"),
+        )),
+    }
+}
+
+fn literal_from_text(ty: &Type, text: &str) -> Expr {
+    match *ty {
+        Type::BOOL => Expr::Value(AstValue::Boolean(text == "t" || text == "true")),
+        Type::INT2 | Type::INT4 | Type::INT8 | Type::FLOAT4 | Type::FLOAT8 | Type::NUMERIC => {
+            number_literal(text)
+        }
+        _ => Expr::Value(AstValue::SingleQuotedString(text.to_owned())),
+    }
+}
+
+fn decode_param(raw: Option<&[u8]>, ty: &Type, format: FieldFormat) -> PgWireResult<Expr> {
+    let Some(bytes) = raw else {
+        return Ok(Expr::Value(AstValue::Null));
+    };
+
+    match format {
+        FieldFormat::Text => std::str::from_utf8(bytes)
+            .map(|text| literal_from_text(ty, text))
+            .map_err(|e| PgWireError::ApiError(Box::new(e))),
+        FieldFormat::Binary => decode_binary_param(ty, bytes),
+    }
+}
+
+/// Replaces each `$n` placeholder in `stmt` with the typed literal decoded
+/// from the corresponding `Bind` parameter, so the rewritten `Statement`
+/// can be dispatched to a peer exactly like a literal-only query.
+struct ParamSubstitutor {
+    literals: Vec<Expr>,
+    error: Option<PgWireError>,
+}
+
+impl VisitorMut for ParamSubstitutor {
+    type Break = ();
+
+    fn pre_visit_expr(&mut self, expr: &mut Expr) -> ControlFlow<Self::Break> {
+        if let Expr::Value(AstValue::Placeholder(name)) = expr {
+            match name.trim_start_matches('$').parse::<usize>() {
+                Ok(index) if index >= 1 && index <= self.literals.len() => {
+                    *expr = self.literals[index - 1].clone();
+                }
+                _ => {
+                    self.error.get_or_insert_with(|| {
+                        nexus_error(
+                            SqlState::InvalidPreparedStatementDefinition,
+                            format!("invalid or out-of-range parameter placeholder {name}"),
+                        )
+                    });
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Substitutes the placeholders in `stmt` with the values bound by a
+/// Postgres `Bind` message, honoring its per-parameter format codes
+/// (see [`format_for`]).
+pub fn substitute_params(
+    stmt: &Statement,
+    param_types: &[Type],
+    formats: &[FieldFormat],
+    raw_values: &[Option<Vec<u8>>],
+) -> PgWireResult<Statement> {
+    let mut literals = Vec::with_capacity(raw_values.len());
+    for (i, raw) in raw_values.iter().enumerate() {
+        let ty = param_types.get(i).unwrap_or(&Type::UNKNOWN);
+        literals.push(decode_param(raw.as_deref(), ty, format_for(formats, i))?);
+    }
+
+    let mut stmt = stmt.clone();
+    let mut substitutor = ParamSubstitutor {
+        literals,
+        error: None,
+    };
+    let _ = stmt.visit(&mut substitutor);
+    if let Some(err) = substitutor.error {
+        return Err(err);
+    }
+
+    Ok(stmt)
+}
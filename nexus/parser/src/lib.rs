@@ -1,4 +1,4 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, ops::ControlFlow, sync::Arc};
 
 use analyzer::{
     CursorEvent, PeerCursorAnalyzer, PeerDDL, PeerDDLAnalyzer, PeerExistanceAnalyzer,
@@ -10,10 +10,62 @@ use pgwire::{
     api::{ClientInfo, Type, stmt::QueryParser},
     error::{ErrorInfo, PgWireError, PgWireResult},
 };
-use sqlparser::{ast::Statement, dialect::PostgreSqlDialect, parser::Parser};
+use sqlparser::{
+    ast::{Expr, Statement, Value as AstValue, Visit, Visitor},
+    dialect::PostgreSqlDialect,
+    parser::Parser,
+};
+
+mod bind;
+mod sqlstate;
+
+pub use bind::substitute_params;
+pub use sqlstate::SqlState;
 
 const DIALECT: PostgreSqlDialect = PostgreSqlDialect {};
 
+/// Builds a `PgWireError` carrying the given SQLSTATE code, matching what
+/// `psql` and other clients expect to see in `ErrorResponse`.
+pub(crate) fn nexus_error(state: SqlState, msg: impl Into<String>) -> PgWireError {
+    PgWireError::UserError(Box::new(ErrorInfo::new(
+        "ERROR".to_owned(),
+        state.code().to_owned(),
+        msg.into(),
+    )))
+}
+
+/// Walks a parsed [`Statement`] and records the highest-numbered `$n`
+/// placeholder it finds, so we know how many bind parameters to expect.
+#[derive(Debug, Clone, Default)]
+struct PlaceholderCollector {
+    max_index: usize,
+}
+
+impl Visitor for PlaceholderCollector {
+    type Break = ();
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<Self::Break> {
+        if let Expr::Value(AstValue::Placeholder(name)) = expr {
+            if let Ok(index) = name.trim_start_matches('$').parse::<usize>() {
+                self.max_index = self.max_index.max(index);
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Infers the type of each `$n` placeholder in `stmt`, preferring the types
+/// the client declared during `Parse` and falling back to `Type::UNKNOWN`
+/// for anything it left unspecified.
+fn infer_param_types(stmt: &Statement, client_types: &[Type]) -> Vec<Type> {
+    let mut collector = PlaceholderCollector::default();
+    let _ = stmt.visit(&mut collector);
+
+    (0..collector.max_index)
+        .map(|i| client_types.get(i).cloned().unwrap_or(Type::UNKNOWN))
+        .collect()
+}
+
 #[derive(Clone)]
 pub struct NexusQueryParser {
     catalog: Arc<Catalog>,
@@ -44,13 +96,9 @@ impl NexusStatement {
         peers: HashMap<String, pt::peerdb_peers::Peer>,
         stmt: &Statement,
     ) -> PgWireResult<Self> {
-        let ddl = PeerDDLAnalyzer.analyze(stmt).map_err(|e| {
-            PgWireError::UserError(Box::new(ErrorInfo::new(
-                "ERROR".to_owned(),
-                "internal_error".to_owned(),
-                e.to_string(),
-            )))
-        })?;
+        let ddl = PeerDDLAnalyzer
+            .analyze(stmt)
+            .map_err(|e| nexus_error(SqlState::InternalError, e.to_string()))?;
 
         if let Some(ddl) = ddl {
             return Ok(NexusStatement::PeerDDL {
@@ -68,13 +116,8 @@ impl NexusStatement {
 
         let assoc = {
             let pea = PeerExistanceAnalyzer::new(&peers);
-            pea.analyze(stmt).map_err(|e| {
-                PgWireError::UserError(Box::new(ErrorInfo::new(
-                    "ERROR".to_owned(),
-                    "feature_not_supported".to_owned(),
-                    e.to_string(),
-                )))
-            })
+            pea.analyze(stmt)
+                .map_err(|e| nexus_error(SqlState::FeatureNotSupported, e.to_string()))
         }?;
 
         Ok(NexusStatement::PeerQuery {
@@ -86,8 +129,16 @@ impl NexusStatement {
 
 #[derive(Debug, Clone)]
 pub struct NexusParsedStatement {
-    pub statement: NexusStatement,
+    /// One entry per semicolon-separated statement in the original query.
+    /// The extended protocol only ever produces a single entry, since a
+    /// `Parse` message is not allowed to carry more than one statement;
+    /// the simple query protocol may produce several.
+    pub statements: Vec<NexusStatement>,
     pub query: String,
+    /// Types of the `$n` placeholders in `statements`, in order. Always
+    /// empty for statements parsed via the simple query protocol, which
+    /// has no notion of bind parameters.
+    pub param_types: Vec<Type>,
 }
 
 impl NexusQueryParser {
@@ -98,47 +149,38 @@ impl NexusQueryParser {
     pub async fn get_peers_bridge(&self) -> PgWireResult<HashMap<String, pt::peerdb_peers::Peer>> {
         let peers = self.catalog.get_peers().await;
 
-        peers.map_err(|e| {
-            PgWireError::UserError(Box::new(ErrorInfo::new(
-                "ERROR".to_owned(),
-                "internal_error".to_owned(),
-                e.to_string(),
-            )))
-        })
+        peers.map_err(|e| nexus_error(SqlState::InternalError, e.to_string()))
     }
 
     pub async fn parse_simple_sql(&self, sql: &str) -> PgWireResult<NexusParsedStatement> {
-        let mut stmts =
-            Parser::parse_sql(&DIALECT, sql).map_err(|e| PgWireError::ApiError(Box::new(e)))?;
-        if stmts.len() > 1 {
-            let err_msg = format!("unsupported sql: {sql}, statements: {stmts:?}");
-            // TODO (kaushik): Better error message for this. When do we start seeing multiple statements?
-            Err(PgWireError::UserError(Box::new(ErrorInfo::new(
-                "ERROR".to_owned(),
-                "42P14".to_owned(),
-                err_msg,
-            ))))
-        } else if stmts.is_empty() {
-            Ok(NexusParsedStatement {
-                statement: NexusStatement::Empty,
-                query: sql.to_owned(),
-            })
+        let stmts = Parser::parse_sql(&DIALECT, sql)
+            .map_err(|e| nexus_error(SqlState::SyntaxError, e.to_string()))?;
+
+        // Postgres' simple query protocol allows several semicolon-separated
+        // statements in one message (e.g. `BEGIN; SELECT ...; COMMIT;`),
+        // unlike the extended protocol's `Parse`, so classify each one
+        // independently rather than rejecting the batch outright.
+        let statements = if stmts.is_empty() {
+            vec![NexusStatement::Empty]
         } else {
-            let stmt = stmts.remove(0);
-            if matches!(stmt, Statement::Rollback { .. }) {
-                Ok(NexusParsedStatement {
-                    statement: NexusStatement::Rollback { stmt },
-                    query: sql.to_owned(),
-                })
-            } else {
-                let peers = self.get_peers_bridge().await?;
-                let nexus_stmt = NexusStatement::new(peers, &stmt)?;
-                Ok(NexusParsedStatement {
-                    statement: nexus_stmt,
-                    query: sql.to_owned(),
-                })
+            let peers = self.get_peers_bridge().await?;
+            let mut statements = Vec::with_capacity(stmts.len());
+            for stmt in stmts {
+                let nexus_stmt = if matches!(stmt, Statement::Rollback { .. }) {
+                    NexusStatement::Rollback { stmt }
+                } else {
+                    NexusStatement::new(peers.clone(), &stmt)?
+                };
+                statements.push(nexus_stmt);
             }
-        }
+            statements
+        };
+
+        Ok(NexusParsedStatement {
+            statements,
+            query: sql.to_owned(),
+            param_types: Vec::new(),
+        })
     }
 }
 
@@ -150,32 +192,36 @@ impl QueryParser for NexusQueryParser {
         &self,
         _client: &C,
         sql: &str,
-        _types: &[Type],
+        types: &[Type],
     ) -> PgWireResult<Self::Statement>
     where
         C: ClientInfo + Unpin + Send + Sync,
     {
-        let mut stmts =
-            Parser::parse_sql(&DIALECT, sql).map_err(|e| PgWireError::ApiError(Box::new(e)))?;
+        let mut stmts = Parser::parse_sql(&DIALECT, sql)
+            .map_err(|e| nexus_error(SqlState::SyntaxError, e.to_string()))?;
         if stmts.len() > 1 {
+            // The extended protocol's Parse message legitimately forbids
+            // multiple statements in one string, unlike simple query.
             let err_msg = format!("unsupported sql: {sql}, statements: {stmts:?}");
-            Err(PgWireError::UserError(Box::new(ErrorInfo::new(
-                "ERROR".to_owned(),
-                "42P14".to_owned(),
+            Err(nexus_error(
+                SqlState::InvalidPreparedStatementDefinition,
                 err_msg,
-            ))))
+            ))
         } else if stmts.is_empty() {
             Ok(NexusParsedStatement {
-                statement: NexusStatement::Empty,
+                statements: vec![NexusStatement::Empty],
                 query: sql.to_owned(),
+                param_types: Vec::new(),
             })
         } else {
             let stmt = stmts.remove(0);
+            let param_types = infer_param_types(&stmt, types);
             let peers = self.get_peers_bridge().await?;
             let nexus_stmt = NexusStatement::new(peers, &stmt)?;
             Ok(NexusParsedStatement {
-                statement: nexus_stmt,
+                statements: vec![nexus_stmt],
                 query: sql.to_owned(),
+                param_types,
             })
         }
     }
@@ -0,0 +1,103 @@
+//! Typed SQLSTATE codes, generated from a table of commonly used codes
+//! taken from the Postgres `errcodes.txt` list. The table covers a subset
+//! of the standard classes; any other code maps to `SqlState::Other`.
+
+/// Declares the `SqlState` enum plus its code/lookup plumbing from a table
+/// of `Variant => "code"` entries, so adding a class is a one-line change
+/// instead of hand-writing another set of match arms.
+macro_rules! sqlstates {
+    ($($variant:ident => $code:literal),+ $(,)?) => {
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum SqlState {
+            $($variant,)+
+            /// Any SQLSTATE not in the table above, kept verbatim.
+            Other(String),
+        }
+
+        impl SqlState {
+            pub fn code(&self) -> &str {
+                match self {
+                    $(Self::$variant => $code,)+
+                    Self::Other(code) => code,
+                }
+            }
+
+            pub fn from_code(code: &str) -> Self {
+                static CODES: phf::Map<&'static str, SqlState> = phf::phf_map! {
+                    $($code => SqlState::$variant,)+
+                };
+                CODES
+                    .get(code)
+                    .cloned()
+                    .unwrap_or_else(|| Self::Other(code.to_owned()))
+            }
+        }
+    };
+}
+
+sqlstates! {
+    // Class 00 — Successful Completion
+    SuccessfulCompletion => "00000",
+    // Class 01 — Warning
+    WarningDynamicResultSetsReturned => "0100C",
+    // Class 02 — No Data
+    NoData => "02000",
+    // Class 08 — Connection Exception
+    ConnectionException => "08000",
+    ConnectionDoesNotExist => "08003",
+    ConnectionFailure => "08006",
+    // Class 0A — Feature Not Supported
+    FeatureNotSupported => "0A000",
+    // Class 22 — Data Exception
+    DataException => "22000",
+    StringDataRightTruncation => "22001",
+    DivisionByZero => "22012",
+    InvalidTextRepresentation => "22P02",
+    InvalidBinaryRepresentation => "22P03",
+    // Class 23 — Integrity Constraint Violation
+    IntegrityConstraintViolation => "23000",
+    NotNullViolation => "23502",
+    ForeignKeyViolation => "23503",
+    UniqueViolation => "23505",
+    CheckViolation => "23514",
+    // Class 25 — Invalid Transaction State
+    InvalidTransactionState => "25000",
+    ActiveSqlTransaction => "25001",
+    NoActiveSqlTransaction => "25P01",
+    // Class 28 — Invalid Authorization Specification
+    InvalidAuthorizationSpecification => "28000",
+    InvalidPassword => "28P01",
+    // Class 2D — Invalid Transaction Termination
+    InvalidTransactionTermination => "2D000",
+    // Class 34 — Invalid Cursor Name
+    InvalidCursorName => "34000",
+    // Class 38 — External Routine Exception
+    ExternalRoutineException => "38000",
+    // Class 3D — Invalid Catalog Name
+    InvalidCatalogName => "3D000",
+    // Class 3F — Invalid Schema Name
+    InvalidSchemaName => "3F000",
+    // Class 40 — Transaction Rollback
+    TransactionRollback => "40000",
+    SerializationFailure => "40001",
+    // Class 42 — Syntax Error or Access Rule Violation
+    SyntaxErrorOrAccessRuleViolation => "42000",
+    SyntaxError => "42601",
+    InsufficientPrivilege => "42501",
+    UndefinedColumn => "42703",
+    UndefinedTable => "42P01",
+    DuplicateColumn => "42701",
+    DuplicateTable => "42P07",
+    InvalidPreparedStatementDefinition => "42P14",
+    AmbiguousColumn => "42702",
+    // Class 53 — Insufficient Resources
+    InsufficientResources => "53000",
+    TooManyConnections => "53300",
+    // Class 57 — Operator Intervention
+    OperatorIntervention => "57000",
+    QueryCanceled => "57014",
+    // Class 58 — System Error
+    SystemError => "58000",
+    // Class XX — Internal Error
+    InternalError => "XX000",
+}
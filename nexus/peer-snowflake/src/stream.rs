@@ -12,14 +12,20 @@ use pgwire::{
 use secrecy::ExposeSecret;
 use serde::Deserialize;
 use std::{
+    collections::VecDeque,
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
 };
+use tokio::task::JoinHandle;
 use value::Value::{
     self, BigInt, Binary, Bool, Date, Float, PostgresTimestamp, Text, Time, TimestampWithTimeZone,
 };
 
+/// Number of partitions to keep fetching ahead of the one currently being
+/// served, so stream consumption never blocks on a fresh round trip.
+const PARTITION_PREFETCH_DEPTH: usize = 3;
+
 #[derive(Clone, Deserialize, Debug)]
 #[serde(rename_all = "lowercase")]
 pub(crate) enum SnowflakeDataType {
@@ -37,6 +43,48 @@ pub(crate) enum SnowflakeDataType {
     #[serde(rename = "timestamp_tz")]
     TimestampTz,
     Variant,
+    Array,
+    Object,
+    Geography,
+    Geometry,
+}
+
+/// Resolves the wire format of result column `i` from the format codes
+/// carried by a `Bind` message, which may supply zero codes (all text),
+/// one code (applied to every column), or one code per column.
+#[derive(Clone, Debug)]
+pub enum FormatIterator {
+    AllText,
+    All(FieldFormat),
+    PerColumn(Vec<FieldFormat>),
+}
+
+impl FormatIterator {
+    pub fn from_codes(codes: &[i16]) -> Self {
+        match codes {
+            [] => Self::AllText,
+            [code] => Self::All(format_from_code(*code)),
+            codes => Self::PerColumn(codes.iter().copied().map(format_from_code).collect()),
+        }
+    }
+
+    fn format_for(&self, column: usize) -> FieldFormat {
+        match self {
+            Self::AllText => FieldFormat::Text,
+            Self::All(format) => *format,
+            Self::PerColumn(formats) => formats.get(column).copied().unwrap_or(FieldFormat::Text),
+        }
+    }
+}
+
+impl Default for FormatIterator {
+    fn default() -> Self {
+        Self::AllText
+    }
+}
+
+fn format_from_code(code: i16) -> FieldFormat {
+    if code == 0 { FieldFormat::Text } else { FieldFormat::Binary }
 }
 
 #[derive(Clone)]
@@ -57,19 +105,24 @@ fn convert_field_type(field_type: &SnowflakeDataType) -> Type {
         SnowflakeDataType::TimestampNtz => Type::TIMESTAMP,
         SnowflakeDataType::TimestampTz => Type::TIMESTAMPTZ,
         SnowflakeDataType::Variant => Type::JSONB,
+        SnowflakeDataType::Array => Type::JSONB,
+        SnowflakeDataType::Object => Type::JSONB,
+        SnowflakeDataType::Geography => Type::TEXT,
+        SnowflakeDataType::Geometry => Type::TEXT,
     }
 }
 
 impl SnowflakeSchema {
-    pub fn from_result_set(result_set: &ResultSet) -> Self {
+    pub fn from_result_set(result_set: &ResultSet, formats: &FormatIterator) -> Self {
         let fields = result_set.resultSetMetaData.rowType.clone();
 
         let schema = Arc::new(
             fields
                 .iter()
-                .map(|field| {
+                .enumerate()
+                .map(|(i, field)| {
                     let datatype = convert_field_type(&field.r#type);
-                    FieldInfo::new(field.name.clone(), None, None, datatype, FieldFormat::Text)
+                    FieldInfo::new(field.name.clone(), None, None, datatype, formats.format_for(i))
                 })
                 .collect(),
         );
@@ -92,8 +145,13 @@ pub struct SnowflakeRecordStreamInner {
     partition_index: usize,
     partition_number: usize,
     endpoint_url: String,
-    auth: SnowflakeAuth,
+    auth: Arc<SnowflakeAuth>,
     schema: SnowflakeSchema,
+    client: reqwest::Client,
+    /// In-flight fetches for the partitions immediately after
+    /// `partition_number`, in order, up to `PARTITION_PREFETCH_DEPTH` deep.
+    prefetch_queue: VecDeque<JoinHandle<anyhow::Result<PartitionResult>>>,
+    next_fetch_partition: usize,
 }
 
 impl SnowflakeRecordStream {
@@ -103,17 +161,23 @@ impl SnowflakeRecordStream {
         partition_number: usize,
         endpoint_url: String,
         auth: SnowflakeAuth,
+        formats: FormatIterator,
     ) -> SnowflakeRecordStream {
-        let schema = SnowflakeSchema::from_result_set(&result_set);
+        let schema = SnowflakeSchema::from_result_set(&result_set, &formats);
 
-        let inner = SnowflakeRecordStreamInner {
+        let mut inner = SnowflakeRecordStreamInner {
             result_set,
             partition_index,
             partition_number,
             endpoint_url,
-            auth,
+            auth: Arc::new(auth),
             schema: schema.clone(),
+            client: reqwest::Client::new(),
+            prefetch_queue: VecDeque::with_capacity(PARTITION_PREFETCH_DEPTH),
+            next_fetch_partition: partition_number + 1,
         };
+        inner.fill_prefetch_queue();
+
         let stream = futures::stream::unfold(inner, async |mut inner| {
             inner.advance().await.map(|val| (val, inner))
         });
@@ -125,6 +189,30 @@ impl SnowflakeRecordStream {
     }
 }
 
+async fn fetch_partition(
+    client: reqwest::Client,
+    auth: Arc<SnowflakeAuth>,
+    endpoint_url: String,
+    statement_handle: String,
+    partition_number: usize,
+) -> anyhow::Result<PartitionResult> {
+    let headers = auth.headers()?;
+    client
+        .get(format!("{endpoint_url}/{statement_handle}"))
+        .query(&[("partition", partition_number.to_string())])
+        .header(
+            "Authorization",
+            format!("Bearer {}", headers.bearer_token.expose_secret()),
+        )
+        .header("X-Snowflake-Authorization-Token-Type", headers.token_type)
+        .header("user-agent", "ureq")
+        .send()
+        .await?
+        .json::<PartitionResult>()
+        .await
+        .map_err(|_| anyhow::anyhow!("get_partition failed"))
+}
+
 impl SnowflakeRecordStreamInner {
     pub fn convert_result_set_item(&mut self) -> anyhow::Result<Record> {
         let mut row_values = Vec::new();
@@ -150,7 +238,6 @@ impl SnowflakeRecordStreamInner {
                         SnowflakeDataType::Binary => Binary(hex::decode(elem)?.into()),
                         SnowflakeDataType::Boolean => Bool(elem.parse()?),
                         SnowflakeDataType::Date => {
-                            println!("Entered Date. elem: {elem:#?}");
                             Date(NaiveDate::parse_from_str(elem, DATE_PARSE_FORMAT)?)
                         }
                         SnowflakeDataType::Time => {
@@ -198,10 +285,15 @@ impl SnowflakeRecordStreamInner {
                                 ),
                             }
                         }
-                        SnowflakeDataType::Variant => {
+                        SnowflakeDataType::Variant
+                        | SnowflakeDataType::Array
+                        | SnowflakeDataType::Object => {
                             let jsonb: serde_json::Value = serde_json::from_str(elem)?;
                             Value::JsonB(jsonb)
                         }
+                        SnowflakeDataType::Geography | SnowflakeDataType::Geometry => {
+                            Text(elem.to_string())
+                        }
                     },
                 ),
             };
@@ -217,29 +309,43 @@ impl SnowflakeRecordStreamInner {
         })
     }
 
+    fn partition_count(&self) -> usize {
+        self.result_set.resultSetMetaData.partitionInfo.len()
+    }
+
+    /// Tops up `prefetch_queue` so that fetches for up to
+    /// `PARTITION_PREFETCH_DEPTH` partitions beyond the one currently being
+    /// served are already in flight.
+    fn fill_prefetch_queue(&mut self) {
+        let statement_handle = self.result_set.statementHandle.clone();
+        while self.prefetch_queue.len() < PARTITION_PREFETCH_DEPTH
+            && self.next_fetch_partition < self.partition_count()
+        {
+            let handle = tokio::spawn(fetch_partition(
+                self.client.clone(),
+                self.auth.clone(),
+                self.endpoint_url.clone(),
+                statement_handle.clone(),
+                self.next_fetch_partition,
+            ));
+            self.prefetch_queue.push_back(handle);
+            self.next_fetch_partition += 1;
+        }
+    }
+
     async fn advance_partition(&mut self) -> anyhow::Result<bool> {
-        if (self.partition_number + 1) == self.result_set.resultSetMetaData.partitionInfo.len() {
+        if (self.partition_number + 1) == self.partition_count() {
             return Ok(false);
         }
         self.partition_number += 1;
         self.partition_index = 0;
-        let partition_number = self.partition_number;
-        let secret = self.auth.get_jwt()?.expose_secret();
-        let statement_handle = self.result_set.statementHandle.clone();
-        let url = self.endpoint_url.clone();
-        println!("Secret: {secret:#?}");
-        let response = reqwest::Client::new()
-            .get(format!("{url}/{statement_handle}"))
-            .query(&[("partition", partition_number.to_string())])
-            .header("Authorization", format!("Bearer {secret}"))
-            .header("X-Snowflake-Authorization-Token-Type", "KEYPAIR_JWT")
-            .header("user-agent", "ureq")
-            .send()
-            .await?
-            .json::<PartitionResult>()
-            .await
-            .map_err(|_| anyhow::anyhow!("get_partition failed"))?;
-        println!("Response: {:#?}", response.data);
+
+        let handle = self
+            .prefetch_queue
+            .pop_front()
+            .expect("prefetch queue should always cover the next partition");
+        let response = handle.await??;
+        self.fill_prefetch_queue();
 
         self.result_set.data = response.data;
         Ok(true)